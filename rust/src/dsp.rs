@@ -0,0 +1,184 @@
+//! Cascaded biquad IIR filtering, used to band-limit the synthetic signal
+//! and noise produced by [`crate::radio::SignalGenerator`] instead of
+//! emitting flat white noise plus a pure tone.
+
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+/// Direct-Form-II-Transposed biquad coefficients for the recurrence
+/// `y = b0*x + s1; s1 = b1*x - a1*y + s2; s2 = b2*x - a2*y`.
+#[derive(Debug, Clone, Copy)]
+pub struct BiquadCoeffs {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl BiquadCoeffs {
+    /// RBJ Audio EQ Cookbook low-pass, normalized so `a0 == 1`.
+    pub fn low_pass(sample_rate: f64, cutoff_hz: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * cutoff_hz / sample_rate;
+        let (sin_w0, cos_w0) = (w0.sin(), w0.cos());
+        let alpha = sin_w0 / (2.0 * q);
+
+        let a0 = 1.0 + alpha;
+        let b0 = (1.0 - cos_w0) / 2.0 / a0;
+        let b1 = (1.0 - cos_w0) / a0;
+        let b2 = b0;
+        let a1 = -2.0 * cos_w0 / a0;
+        let a2 = (1.0 - alpha) / a0;
+
+        Self { b0, b1, b2, a1, a2 }
+    }
+
+    /// RBJ band-pass (constant 0 dB peak gain) centered on `center_hz`.
+    pub fn band_pass(sample_rate: f64, center_hz: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * center_hz / sample_rate;
+        let (sin_w0, cos_w0) = (w0.sin(), w0.cos());
+        let alpha = sin_w0 / (2.0 * q);
+
+        let a0 = 1.0 + alpha;
+        let b0 = alpha / a0;
+        let b1 = 0.0;
+        let b2 = -alpha / a0;
+        let a1 = -2.0 * cos_w0 / a0;
+        let a2 = (1.0 - alpha) / a0;
+
+        Self { b0, b1, b2, a1, a2 }
+    }
+
+    /// RBJ notch centered on `center_hz`.
+    pub fn notch(sample_rate: f64, center_hz: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * center_hz / sample_rate;
+        let (sin_w0, cos_w0) = (w0.sin(), w0.cos());
+        let alpha = sin_w0 / (2.0 * q);
+
+        let a0 = 1.0 + alpha;
+        let b0 = 1.0 / a0;
+        let b1 = -2.0 * cos_w0 / a0;
+        let b2 = b0;
+        let a1 = b1;
+        let a2 = (1.0 - alpha) / a0;
+
+        Self { b0, b1, b2, a1, a2 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    s1: f64,
+    s2: f64,
+}
+
+impl BiquadState {
+    fn process(&mut self, c: &BiquadCoeffs, x: f64) -> f64 {
+        let y = c.b0 * x + self.s1;
+        self.s1 = c.b1 * x - c.a1 * y + self.s2;
+        self.s2 = c.b2 * x - c.a2 * y;
+        y
+    }
+}
+
+/// A cascade of biquad sections applied independently to I and Q, carrying
+/// state across calls so there are no block-boundary discontinuities.
+#[derive(Clone)]
+pub struct IirFilter {
+    sections: Vec<BiquadCoeffs>,
+    i_state: Vec<BiquadState>,
+    q_state: Vec<BiquadState>,
+}
+
+impl IirFilter {
+    pub fn new(sections: Vec<BiquadCoeffs>) -> Self {
+        let n = sections.len();
+        Self {
+            sections,
+            i_state: vec![BiquadState::default(); n],
+            q_state: vec![BiquadState::default(); n],
+        }
+    }
+
+    pub fn low_pass(sample_rate: f64, cutoff_hz: f64, q: f64) -> Self {
+        Self::new(vec![BiquadCoeffs::low_pass(sample_rate, cutoff_hz, q)])
+    }
+
+    pub fn band_pass(sample_rate: f64, center_hz: f64, q: f64) -> Self {
+        Self::new(vec![BiquadCoeffs::band_pass(sample_rate, center_hz, q)])
+    }
+
+    pub fn notch(sample_rate: f64, center_hz: f64, q: f64) -> Self {
+        Self::new(vec![BiquadCoeffs::notch(sample_rate, center_hz, q)])
+    }
+
+    pub fn process(&mut self, sample: Complex<f64>) -> Complex<f64> {
+        let (mut i, mut q) = (sample.re, sample.im);
+        for (idx, coeffs) in self.sections.iter().enumerate() {
+            i = self.i_state[idx].process(coeffs, i);
+            q = self.q_state[idx].process(coeffs, q);
+        }
+        Complex::new(i, q)
+    }
+
+    pub fn process_block(&mut self, samples: &mut [Complex<f64>]) {
+        for s in samples.iter_mut() {
+            *s = self.process(*s);
+        }
+    }
+
+    /// Clears the I/Q filter state. Call this when a DDC's frequency or the
+    /// sample rate changes so old transients don't leak into the new
+    /// configuration.
+    pub fn reset(&mut self) {
+        for s in self.i_state.iter_mut().chain(self.q_state.iter_mut()) {
+            *s = BiquadState::default();
+        }
+    }
+
+    /// Swaps in freshly computed coefficients and resets the filter state,
+    /// cheaper than discarding and reallocating the whole [`IirFilter`] when
+    /// only the sample rate or center/cutoff frequency changed.
+    pub fn reconfigure(&mut self, sections: Vec<BiquadCoeffs>) {
+        if sections.len() != self.sections.len() {
+            let n = sections.len();
+            self.i_state = vec![BiquadState::default(); n];
+            self.q_state = vec![BiquadState::default(); n];
+        }
+        self.sections = sections;
+        self.reset();
+    }
+
+    /// Consumes the filter, returning its coefficients for reuse by
+    /// [`Self::reconfigure`].
+    pub fn into_sections(self) -> Vec<BiquadCoeffs> {
+        self.sections
+    }
+}
+
+/// Selects which response an [`IirFilter`] is built from; `None` disables
+/// filtering entirely (the default).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterResponse {
+    None,
+    LowPass,
+    BandPass,
+    Notch,
+}
+
+impl FilterResponse {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "lowpass" | "lp" => Some(Self::LowPass),
+            "bandpass" | "bp" => Some(Self::BandPass),
+            "notch" => Some(Self::Notch),
+            _ => None,
+        }
+    }
+
+    pub fn all_names() -> &'static [&'static str] {
+        &["none", "lowpass", "bandpass", "notch"]
+    }
+}
@@ -1,11 +1,15 @@
+mod dsp;
+mod mqtt;
 mod protocol1;
 mod radio;
+mod scpi;
 
 use std::sync::Arc;
 
 use clap::Parser;
 use tokio::sync::Mutex;
 
+use dsp::FilterResponse;
 use radio::{EchoBuffer, HpsdrHw, RadioState, SignalGenerator};
 
 #[derive(Parser)]
@@ -31,9 +35,62 @@ struct Cli {
     #[arg(long)]
     echo: bool,
 
+    /// CW envelope attack (key-down) time in milliseconds
+    #[arg(long, default_value = "5.0")]
+    cw_rise_ms: f64,
+
+    /// CW envelope release (key-up) time in milliseconds
+    #[arg(long, default_value = "5.0")]
+    cw_fall_ms: f64,
+
+    /// Gate the test tone through the CW envelope using live PTT instead of
+    /// leaving it open after its initial attack, so key-down/up transitions
+    /// are click-shaped. Off by default: the RX test tone stays continuously
+    /// audible without asserting PTT.
+    #[arg(long)]
+    cw_keyed: bool,
+
+    /// Channel filter response applied to the generated IQ
+    #[arg(long, default_value = "none", value_parser = parse_filter)]
+    filter: FilterResponse,
+
+    /// Low-pass filter cutoff frequency in Hz. Band-pass and notch filters
+    /// ignore it and are centered on the test tone offset (`--freq`).
+    #[arg(long, default_value = "3000.0")]
+    filter_cutoff_hz: f64,
+
+    /// Filter Q factor
+    #[arg(long, default_value = "0.7071")]
+    filter_q: f64,
+
+    /// Shapes the synthetic noise floor itself (independent of `filter`,
+    /// which shapes the combined tone+noise stream)
+    #[arg(long, default_value = "none", value_parser = parse_filter)]
+    noise_shape: FilterResponse,
+
+    /// Noise-shaping cutoff (low-pass) or center (band-pass/notch) frequency in Hz
+    #[arg(long, default_value = "3000.0")]
+    noise_shape_hz: f64,
+
+    /// Noise-shaping filter Q factor
+    #[arg(long, default_value = "0.7071")]
+    noise_shape_q: f64,
+
     /// Enable debug logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// MQTT broker to connect to for runtime control (host:port). Disabled if omitted.
+    #[arg(long)]
+    mqtt_broker: Option<String>,
+
+    /// MQTT topic prefix for control/status messages
+    #[arg(long, default_value = "hpsdr-emu")]
+    mqtt_topic: String,
+
+    /// TCP port for the SCPI control server. Disabled if omitted.
+    #[arg(long)]
+    scpi_port: Option<u16>,
 }
 
 fn parse_radio(s: &str) -> Result<HpsdrHw, String> {
@@ -46,6 +103,16 @@ fn parse_radio(s: &str) -> Result<HpsdrHw, String> {
     })
 }
 
+fn parse_filter(s: &str) -> Result<FilterResponse, String> {
+    FilterResponse::from_name(s).ok_or_else(|| {
+        format!(
+            "unknown filter '{}'. Valid: {}",
+            s,
+            FilterResponse::all_names().join(", ")
+        )
+    })
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -77,7 +144,16 @@ async fn main() {
     let mut state = RadioState::new(cli.radio, mac);
     state.sample_rate = sample_rate;
 
-    let siggen = SignalGenerator::new(sample_rate, cli.freq, cli.noise);
+    let mut siggen = SignalGenerator::with_cw_rates(
+        sample_rate,
+        cli.freq,
+        cli.noise,
+        cli.cw_rise_ms,
+        cli.cw_fall_ms,
+        cli.cw_keyed,
+    );
+    siggen.set_filter(cli.filter, cli.filter_cutoff_hz, cli.filter_q);
+    siggen.set_noise_shape(cli.noise_shape, cli.noise_shape_hz, cli.noise_shape_q);
 
     let echo_buf = if cli.echo {
         Some(EchoBuffer::new(sample_rate))
@@ -93,12 +169,46 @@ async fn main() {
         if cli.echo { "on" } else { "off" },
     );
 
+    let mqtt_cfg = match &cli.mqtt_broker {
+        Some(broker) => match mqtt::MqttConfig::parse_broker(broker) {
+            Ok((host, port)) => {
+                log::info!("MQTT control plane enabled: {}:{} (prefix={})", host, port, cli.mqtt_topic);
+                Some(mqtt::MqttConfig {
+                    broker: host,
+                    port,
+                    topic_prefix: cli.mqtt_topic.clone(),
+                })
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
     let state = Arc::new(Mutex::new(state));
     let siggen = Arc::new(Mutex::new(siggen));
     let echo = Arc::new(Mutex::new(echo_buf));
 
     tokio::select! {
-        _ = protocol1::run_protocol1(state, siggen, echo) => {}
+        _ = protocol1::run_protocol1(Arc::clone(&state), Arc::clone(&siggen), Arc::clone(&echo)) => {}
+        _ = async {
+            match mqtt_cfg {
+                Some(cfg) => {
+                    mqtt::run_mqtt(cfg, Arc::clone(&state), Arc::clone(&siggen), Arc::clone(&echo)).await
+                }
+                None => std::future::pending().await,
+            }
+        } => {}
+        _ = async {
+            match cli.scpi_port {
+                Some(port) => {
+                    scpi::run_scpi(port, Arc::clone(&state), Arc::clone(&siggen), Arc::clone(&echo)).await
+                }
+                None => std::future::pending().await,
+            }
+        } => {}
         _ = tokio::signal::ctrl_c() => {
             log::info!("Shutting down...");
         }
@@ -0,0 +1,243 @@
+//! Optional MQTT telemetry + remote-control bridge.
+//!
+//! When enabled via `--mqtt-broker`, this subsystem lets a test harness
+//! reconfigure the running emulator (tone, noise, per-DDC frequencies, ...)
+//! over `<prefix>/cmd/...` topics without restarting the process, and
+//! mirrors live state - including the synthetic AIN readings `protocol1`
+//! reports to the HPSDR host - out to `<prefix>/telemetry/...` so dashboards
+//! or CI can monitor the emulator without touching the UDP protocol.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use tokio::sync::Mutex;
+
+use crate::radio::{compute_ain_readings, EchoBuffer, RadioState, SignalGenerator};
+
+/// How often (in poll iterations) to republish telemetry while idle.
+const TELEMETRY_PERIOD: u32 = 50;
+
+#[derive(Clone)]
+pub struct MqttConfig {
+    pub broker: String,
+    pub port: u16,
+    pub topic_prefix: String,
+}
+
+impl MqttConfig {
+    /// Split a `--mqtt-broker` value of the form `host:port`.
+    pub fn parse_broker(s: &str) -> Result<(String, u16), String> {
+        let (host, port) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected <host>:<port>, got '{}'", s))?;
+        let port: u16 = port
+            .parse()
+            .map_err(|_| format!("invalid MQTT broker port in '{}'", s))?;
+        Ok((host.to_string(), port))
+    }
+}
+
+/// Runs until the connection is dropped by the caller (e.g. ctrl-c); on
+/// transport errors it logs and keeps retrying rather than exiting, since
+/// losing MQTT shouldn't take down the radio emulation.
+pub async fn run_mqtt(
+    cfg: MqttConfig,
+    state: Arc<Mutex<RadioState>>,
+    siggen: Arc<Mutex<SignalGenerator>>,
+    echo: Arc<Mutex<Option<EchoBuffer>>>,
+) {
+    let mut opts = MqttOptions::new("hpsdr-emu", cfg.broker.clone(), cfg.port);
+    opts.set_keep_alive(Duration::from_secs(10));
+
+    let (client, mut eventloop) = AsyncClient::new(opts, 16);
+
+    let sub_topic = format!("{}/cmd/#", cfg.topic_prefix);
+    if let Err(e) = client.subscribe(&sub_topic, QoS::AtLeastOnce).await {
+        log::error!("MQTT: subscribe to {} failed: {}", sub_topic, e);
+        return;
+    }
+    log::info!(
+        "MQTT: connecting to {}:{}, subscribed to {}",
+        cfg.broker,
+        cfg.port,
+        sub_topic
+    );
+
+    let mut polls_since_telemetry: u32 = 0;
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::Publish(p))) => {
+                let payload = String::from_utf8_lossy(&p.payload).trim().to_string();
+                handle_message(&cfg, &state, &siggen, &echo, &p.topic, &payload).await;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("MQTT: connection error: {}, retrying", e);
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+        }
+
+        polls_since_telemetry += 1;
+        if polls_since_telemetry >= TELEMETRY_PERIOD {
+            polls_since_telemetry = 0;
+            publish_telemetry(&cfg, &client, &state, &siggen, &echo).await;
+        }
+    }
+}
+
+/// Deserialize a control payload: either a bare float or a small JSON object
+/// shaped like `{"value": <number>}`. Returns `None` (rather than panicking)
+/// on anything else so the caller can log and drop the message.
+fn parse_payload(payload: &str) -> Option<f64> {
+    if let Ok(v) = payload.parse::<f64>() {
+        return Some(v);
+    }
+    let obj: serde_json::Value = serde_json::from_str(payload).ok()?;
+    obj.get("value")?.as_f64()
+}
+
+async fn handle_message(
+    cfg: &MqttConfig,
+    state: &Arc<Mutex<RadioState>>,
+    siggen: &Arc<Mutex<SignalGenerator>>,
+    echo: &Arc<Mutex<Option<EchoBuffer>>>,
+    topic: &str,
+    payload: &str,
+) {
+    let Some(subtopic) = topic.strip_prefix(&format!("{}/cmd/", cfg.topic_prefix)) else {
+        return;
+    };
+
+    let value = parse_payload(payload);
+
+    match subtopic {
+        "siggen/tone_hz" => match value {
+            Some(hz) => {
+                let mut sg = siggen.lock().await;
+                let max_offset = sg.sample_rate as f64 / 2.0;
+                sg.tone_offset_hz = hz.clamp(-max_offset, max_offset);
+                log::info!("MQTT: tone_hz -> {:.1}", sg.tone_offset_hz);
+            }
+            None => log::warn!("MQTT: rejected {} payload '{}'", topic, payload),
+        },
+        "siggen/noise" => match value {
+            Some(level) if level >= 0.0 => {
+                siggen.lock().await.noise_level = level;
+                log::info!("MQTT: noise -> {:.3e}", level);
+            }
+            _ => log::warn!("MQTT: rejected {} payload '{}'", topic, payload),
+        },
+        "echo/enabled" => match value {
+            Some(v) => {
+                let enable = v != 0.0;
+                let mut guard = echo.lock().await;
+                if enable && guard.is_none() {
+                    let sample_rate = state.lock().await.sample_rate;
+                    *guard = Some(EchoBuffer::new(sample_rate));
+                    log::info!("MQTT: echo/enabled -> true (buffer created)");
+                } else if !enable && guard.take().is_some() {
+                    log::info!("MQTT: echo/enabled -> false (buffer cleared)");
+                }
+            }
+            None => log::warn!("MQTT: rejected {} payload '{}'", topic, payload),
+        },
+        other if other.starts_with("ddc") && other.ends_with("/freq_hz") => {
+            let idx: Option<usize> = other
+                .strip_prefix("ddc")
+                .and_then(|r| r.strip_suffix("/freq_hz"))
+                .and_then(|n| n.parse().ok());
+            match (idx, value) {
+                (Some(i), Some(hz)) if hz >= 0.0 => {
+                    let mut s = state.lock().await;
+                    if i < s.rx_frequencies.len() {
+                        s.rx_frequencies[i] = hz as u32;
+                        log::info!("MQTT: rx_frequencies[{}] -> {:.0} Hz", i, hz);
+                    } else {
+                        log::warn!("MQTT: rejected {} (DDC index {} out of range)", topic, i);
+                    }
+                }
+                _ => log::warn!("MQTT: rejected {} payload '{}'", topic, payload),
+            }
+        }
+        "tx/drive" => match value {
+            Some(d) if (0.0..=255.0).contains(&d) => {
+                state.lock().await.tx_drive = d as u8;
+                log::info!("MQTT: tx_drive -> {}", d as u8);
+            }
+            _ => log::warn!("MQTT: rejected {} payload '{}'", topic, payload),
+        },
+        _ => log::debug!("MQTT: ignoring unknown topic {}", topic),
+    }
+}
+
+async fn publish_telemetry(
+    cfg: &MqttConfig,
+    client: &AsyncClient,
+    state: &Arc<Mutex<RadioState>>,
+    siggen: &Arc<Mutex<SignalGenerator>>,
+    echo: &Arc<Mutex<Option<EchoBuffer>>>,
+) {
+    let seq = {
+        let mut s = state.lock().await;
+        s.next_seq("mqtt_telemetry")
+    };
+    let (running, ptt, sample_rate, tx_drive, nddc, rx_frequencies) = {
+        let s = state.lock().await;
+        (
+            s.running,
+            s.ptt,
+            s.sample_rate,
+            s.tx_drive,
+            s.nddc as usize,
+            s.rx_frequencies,
+        )
+    };
+    let (tone_hz, noise) = {
+        let sg = siggen.lock().await;
+        (sg.tone_offset_hz, sg.noise_level)
+    };
+    let ain = compute_ain_readings(ptt, tx_drive);
+
+    let echo_coverage: serde_json::Map<String, serde_json::Value> = {
+        let guard = echo.lock().await;
+        match guard.as_ref() {
+            Some(e) => e
+                .recorded_frequencies()
+                .map(|freq| {
+                    let ranges = e.coverage_ranges(freq).unwrap_or(&[]);
+                    (freq.to_string(), serde_json::json!(ranges))
+                })
+                .collect(),
+            None => serde_json::Map::new(),
+        }
+    };
+
+    let telemetry = serde_json::json!({
+        "seq": seq,
+        "running": running,
+        "ptt": ptt,
+        "sample_rate": sample_rate,
+        "tx_drive": tx_drive,
+        "rx_frequencies": &rx_frequencies[..nddc],
+        "tone_hz": tone_hz,
+        "noise": noise,
+        "ain": {
+            "exciter_power": ain.exciter_power,
+            "forward_power": ain.forward_power,
+            "reverse_power": ain.reverse_power,
+            "pa_volts": ain.pa_volts,
+            "pa_amps": ain.pa_amps,
+            "supply_volts": ain.supply_volts,
+        },
+        "echo_coverage": echo_coverage,
+    });
+
+    let topic = format!("{}/telemetry/state", cfg.topic_prefix);
+    if let Err(e) = client
+        .publish(&topic, QoS::AtMostOnce, false, telemetry.to_string())
+        .await
+    {
+        log::warn!("MQTT: publish to {} failed: {}", topic, e);
+    }
+}
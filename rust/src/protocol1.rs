@@ -7,8 +7,8 @@ use tokio::net::UdpSocket;
 use tokio::sync::Mutex;
 
 use crate::radio::{
-    code_to_sample_rate, pack_iq_24bit_into, unpack_tx_iq_16bit, EchoBuffer, RadioState,
-    SignalGenerator,
+    code_to_sample_rate, compute_ain_readings, pack_iq_24bit_into, unpack_tx_iq_16bit, EchoBuffer,
+    RadioState, SignalGenerator, StepAttenuator,
 };
 
 const PORT: u16 = 1024;
@@ -130,6 +130,15 @@ impl Protocol1Server {
                     s.tx_drive = c1;
                 }
             }
+            a if (0x14..0x20).contains(&a) && (a % 2 == 0) => {
+                // RX step attenuators: 0x14=RX0, 0x16=RX1, ... half-dB code in C1[5:0]
+                let ddc_idx = ((a - 0x14) / 2) as usize;
+                let code = c1 & 0x3F;
+                if ddc_idx < s.rx_attenuators.len() && s.rx_attenuators[ddc_idx].db() != code as f64 * 0.5 {
+                    log::info!("P1 RX{} attenuator -> {:.1} dB", ddc_idx, code as f64 * 0.5);
+                    s.rx_attenuators[ddc_idx].set_half_db_code(code);
+                }
+            }
             _ => {}
         }
     }
@@ -167,7 +176,7 @@ impl Protocol1Server {
     // -- Sub-frame building -------------------------------------------------
 
     async fn fill_subframe(&mut self, buf: &mut [u8], offset: usize) {
-        let s = self.state.lock().await;
+        let mut s = self.state.lock().await;
         let nddc = s.nddc.max(1) as usize;
         let spr = 504 / (6 * nddc + 2);
 
@@ -184,6 +193,7 @@ impl Protocol1Server {
         let ptt_bit = if s.ptt { 1u8 } else { 0u8 };
         buf[offset + 3] = c0_addr | 0x80 | ptt_bit;
 
+        let ain = compute_ain_readings(s.ptt, s.tx_drive);
         match c0_addr {
             0x00 => {
                 // C1: ADC overflow (none), C2: Mercury FW, C3: Penny ver, C4: reserved
@@ -194,34 +204,18 @@ impl Protocol1Server {
             }
             0x08 => {
                 // C1-C2: Exciter power (AIN5), C3-C4: Forward power (AIN1)
-                let (exc, fwd) = if s.ptt {
-                    let d = s.tx_drive as u16;
-                    (d * 10, (d * d) >> 4)
-                } else {
-                    (0, 0)
-                };
-                buf[offset + 4..offset + 6].copy_from_slice(&exc.to_be_bytes());
-                buf[offset + 6..offset + 8].copy_from_slice(&fwd.to_be_bytes());
+                buf[offset + 4..offset + 6].copy_from_slice(&ain.exciter_power.to_be_bytes());
+                buf[offset + 6..offset + 8].copy_from_slice(&ain.forward_power.to_be_bytes());
             }
             0x10 => {
                 // C1-C2: Reverse power (AIN2), C3-C4: PA volts (AIN3)
-                let rev = if s.ptt {
-                    let d = s.tx_drive as u16;
-                    let fwd = (d * d) >> 4;
-                    (fwd / 50).max(1)
-                } else {
-                    0
-                };
-                let supply: u16 = 3200;
-                buf[offset + 4..offset + 6].copy_from_slice(&rev.to_be_bytes());
-                buf[offset + 6..offset + 8].copy_from_slice(&supply.to_be_bytes());
+                buf[offset + 4..offset + 6].copy_from_slice(&ain.reverse_power.to_be_bytes());
+                buf[offset + 6..offset + 8].copy_from_slice(&ain.pa_volts.to_be_bytes());
             }
             0x18 => {
                 // C1-C2: PA current (AIN4), C3-C4: Supply volts (AIN6)
-                let pa_amps: u16 = if s.ptt { s.tx_drive as u16 * 5 } else { 0 };
-                let supply: u16 = 3200;
-                buf[offset + 4..offset + 6].copy_from_slice(&pa_amps.to_be_bytes());
-                buf[offset + 6..offset + 8].copy_from_slice(&supply.to_be_bytes());
+                buf[offset + 4..offset + 6].copy_from_slice(&ain.pa_amps.to_be_bytes());
+                buf[offset + 6..offset + 8].copy_from_slice(&ain.supply_volts.to_be_bytes());
             }
             _ => {
                 buf[offset + 4..offset + 8].fill(0);
@@ -230,7 +224,12 @@ impl Protocol1Server {
 
         // Generate IQ samples for each DDC
         let rx_freqs: Vec<u32> = s.rx_frequencies[..nddc].to_vec();
+        let rx_attenuators: Vec<StepAttenuator> = s.rx_attenuators[..nddc].to_vec();
         let sample_rate = s.sample_rate;
+        let ptt = s.ptt;
+        // All DDCs (and the echo buffer) in this sub-frame share one block of
+        // the sample clock so their tones stay phase-coherent with each other.
+        let abs_sample_index = s.advance_sample_clock(spr as u64);
         drop(s); // release state lock before siggen/echo
 
         let mut ddc_samples: Vec<Vec<Complex<f64>>> = Vec::with_capacity(nddc);
@@ -240,19 +239,28 @@ impl Protocol1Server {
             if has_echo {
                 let echo = echo_guard.as_mut().unwrap();
                 for ddc in 0..nddc {
-                    let iq = echo.generate_echo(spr, rx_freqs[ddc], sample_rate);
+                    let iq = echo.generate_echo(spr, rx_freqs[ddc], sample_rate, abs_sample_index);
                     ddc_samples.push(iq);
                 }
             } else {
                 drop(echo_guard);
                 let mut sg = self.siggen.lock().await;
                 for ddc in 0..nddc {
-                    let iq = sg.generate_iq(spr, ddc);
+                    let iq = sg.generate_iq(spr, ddc, ptt, abs_sample_index);
                     ddc_samples.push(iq);
                 }
             }
         }
 
+        // Apply the per-DDC RX step attenuator to the generated/echoed IQ
+        // before packing, so gain commands from the SDR client change the
+        // emitted amplitude.
+        for ddc in 0..nddc {
+            for sample in &mut ddc_samples[ddc] {
+                *sample = rx_attenuators[ddc].apply(*sample);
+            }
+        }
+
         // Pack interleaved: [I(3B) Q(3B)] x nddc + [Mic(2B)] per sample row
         let mut data_offset = offset + 8;
         for row in 0..spr {
@@ -333,27 +341,52 @@ pub async fn run_protocol1(
                 (s.nddc.max(1) as usize, s.sample_rate)
             };
             let spr = 504 / (6 * nddc + 2);
-            let samples_per_packet = spr * 2;
-            let interval = Duration::from_secs_f64(samples_per_packet as f64 / sample_rate as f64);
-
-            let mut timer = tokio::time::interval(interval);
-            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let samples_per_packet = (spr * 2) as u64;
+
+            // Pace packets off an absolute sample count rather than a fixed
+            // tokio::time::interval tick, so rounding error in the per-packet
+            // duration can't accumulate into long-run drift. If the host
+            // changes the sample rate mid-stream we rebase stream_start and
+            // samples_emitted at the current instant instead of carrying the
+            // old rate's schedule forward.
+            let mut stream_start = tokio::time::Instant::now();
+            let mut samples_emitted: u64 = 0;
+            let mut paced_sample_rate = sample_rate;
+            let mut next_deadline = stream_start;
 
             loop {
                 tokio::select! {
-                    _ = timer.tick() => {
-                        let is_running = state.lock().await.running;
+                    _ = tokio::time::sleep_until(next_deadline) => {
+                        let (is_running, current_sample_rate) = {
+                            let s = state.lock().await;
+                            (s.running, s.sample_rate)
+                        };
                         if !is_running {
                             streaming = false;
                             log::info!("P1 Streaming stopped");
                             break;
                         }
+                        if current_sample_rate != paced_sample_rate {
+                            log::info!(
+                                "P1 Sample rate changed {} -> {} Hz mid-stream, rebasing pacing",
+                                paced_sample_rate,
+                                current_sample_rate
+                            );
+                            paced_sample_rate = current_sample_rate;
+                            stream_start = tokio::time::Instant::now();
+                            samples_emitted = 0;
+                        }
                         if let Some(addr) = client_addr {
                             let packet = server.build_data_packet().await;
                             if let Err(e) = socket.send_to(&packet, addr).await {
                                 log::error!("P1 Send error: {}", e);
                             }
                         }
+                        samples_emitted += samples_per_packet;
+                        next_deadline = stream_start
+                            + Duration::from_secs_f64(
+                                samples_emitted as f64 / paced_sample_rate as f64,
+                            );
                     }
                     result = socket.recv_from(&mut recv_buf) => {
                         match result {
@@ -6,6 +6,23 @@ use num_complex::Complex;
 use rand::Rng;
 use rand_distr::{Distribution, Normal};
 
+use crate::dsp::{FilterResponse, IirFilter};
+
+// ---------------------------------------------------------------------------
+// Sample-accurate duration type
+// ---------------------------------------------------------------------------
+
+/// Femtosecond-precision duration, used so echo buffer lengths are computed
+/// from exact integer arithmetic instead of accumulating `f64` rounding.
+/// Falls back to `u64` on wasm32, where 128-bit integer ops are emulated and
+/// comparatively expensive.
+#[cfg(not(target_arch = "wasm32"))]
+pub type FemtoDuration = u128;
+#[cfg(target_arch = "wasm32")]
+pub type FemtoDuration = u64;
+
+pub const FEMTOS_PER_SEC: FemtoDuration = 1_000_000_000_000_000;
+
 // ---------------------------------------------------------------------------
 // HPSDRHW enum
 // ---------------------------------------------------------------------------
@@ -128,6 +145,48 @@ pub fn code_to_sample_rate(code: u8) -> Option<u32> {
     None
 }
 
+// ---------------------------------------------------------------------------
+// StepAttenuator
+// ---------------------------------------------------------------------------
+
+/// Models an HPSDR front-end step attenuator: 0-31.5 dB in 0.5 dB steps,
+/// stored as a half-dB code (0-63) decoded from the Protocol 1 C&C bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct StepAttenuator {
+    half_db_code: u8,
+}
+
+impl StepAttenuator {
+    pub fn new() -> Self {
+        Self { half_db_code: 0 }
+    }
+
+    /// Set the attenuation from a raw half-dB code, clamped to the 0-31.5 dB
+    /// range the hardware supports.
+    pub fn set_half_db_code(&mut self, code: u8) {
+        self.half_db_code = code.min(63);
+    }
+
+    pub fn db(&self) -> f64 {
+        self.half_db_code as f64 * 0.5
+    }
+
+    /// Linear amplitude multiplier for the current setting.
+    pub fn linear_gain(&self) -> f64 {
+        10f64.powf(-self.db() / 20.0)
+    }
+
+    pub fn apply(&self, sample: Complex<f64>) -> Complex<f64> {
+        sample * self.linear_gain()
+    }
+}
+
+impl Default for StepAttenuator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ---------------------------------------------------------------------------
 // RadioState
 // ---------------------------------------------------------------------------
@@ -146,6 +205,12 @@ pub struct RadioState {
     pub tx_drive: u8,
     pub running: bool,
     pub ptt: bool,
+    pub rx_attenuators: [StepAttenuator; 12],
+    /// Absolute sample index, advanced by exactly the number of samples
+    /// emitted per stream. Shared by every DDC and the echo buffer so
+    /// cross-channel phase relationships never depend on call order or
+    /// accumulated per-call rounding.
+    sample_clock: u64,
     seq: HashMap<String, u32>,
 }
 
@@ -165,6 +230,8 @@ impl RadioState {
             tx_drive: 0,
             running: false,
             ptt: false,
+            rx_attenuators: [StepAttenuator::new(); 12],
+            sample_clock: 0,
             seq: HashMap::new(),
         }
     }
@@ -176,6 +243,19 @@ impl RadioState {
         ret
     }
 
+    /// Current absolute sample index, without advancing it.
+    pub fn sample_clock(&self) -> u64 {
+        self.sample_clock
+    }
+
+    /// Returns the sample index at the start of the block and advances the
+    /// clock by `n_samples` for the next caller.
+    pub fn advance_sample_clock(&mut self, n_samples: u64) -> u64 {
+        let start = self.sample_clock;
+        self.sample_clock = self.sample_clock.wrapping_add(n_samples);
+        start
+    }
+
     pub fn random_mac() -> [u8; 6] {
         let mut rng = rand::thread_rng();
         let mut mac = [0u8; 6];
@@ -197,55 +277,409 @@ impl RadioState {
 // SignalGenerator
 // ---------------------------------------------------------------------------
 
+/// 2^64 as an `f64`, used to convert between turns and the full-width phase
+/// accumulator.
+const PHASE_SCALE: f64 = 1.8446744073709552e19; // 2^64
+
+/// Top attenuation code (silent). 10-bit, matching the logarithmic envelope
+/// generators found in FM synthesis chips: 0 is full scale, larger codes are
+/// quieter, and each code step is 0.09375 dB.
+const ENVELOPE_MAX_ATTEN: u16 = 0x3FF;
+const ENVELOPE_DB_PER_STEP: f64 = 0.09375;
+
+/// Gates the test tone through an attack/release attenuation ramp instead of
+/// switching it instantly, so keying the emulated CW carrier doesn't produce
+/// broadband key clicks. Attenuation is tracked as a 10-bit code updated once
+/// every `2^shift` samples, mirroring the rate/shift envelope generators used
+/// in FM synthesis chips.
+struct CwEnvelope {
+    attenuation: u16,
+    counter: u32,
+    rise_shift: u8,
+    rise_inc: u16,
+    fall_shift: u8,
+    fall_inc: u16,
+}
+
+impl CwEnvelope {
+    fn new(sample_rate: u32, rise_ms: f64, fall_ms: f64) -> Self {
+        let (rise_shift, rise_inc) = Self::rate_for(sample_rate, rise_ms);
+        let (fall_shift, fall_inc) = Self::rate_for(sample_rate, fall_ms);
+        Self {
+            attenuation: ENVELOPE_MAX_ATTEN, // starts key-up (silent)
+            counter: 0,
+            rise_shift,
+            rise_inc,
+            fall_shift,
+            fall_inc,
+        }
+    }
+
+    /// Pick an update-tick shift and per-tick increment so the full 10-bit
+    /// attenuation range is swept in roughly `ms` milliseconds.
+    fn rate_for(sample_rate: u32, ms: f64) -> (u8, u16) {
+        let samples = (ms / 1000.0 * sample_rate as f64).max(1.0);
+        let shift = (samples / 16.0).log2().max(0.0) as u8;
+        let ticks = (samples / (1u32 << shift) as f64).max(1.0);
+        let inc = (ENVELOPE_MAX_ATTEN as f64 / ticks)
+            .ceil()
+            .clamp(1.0, ENVELOPE_MAX_ATTEN as f64) as u16;
+        (shift, inc)
+    }
+
+    /// Advance the envelope by one sample and return the linear gain to
+    /// apply to that sample.
+    fn step(&mut self, keyed: bool) -> f64 {
+        self.counter = self.counter.wrapping_add(1);
+        let (shift, inc) = if keyed {
+            (self.rise_shift, self.rise_inc)
+        } else {
+            (self.fall_shift, self.fall_inc)
+        };
+        if self.counter & ((1u32 << shift) - 1) == 0 {
+            if keyed {
+                // Soft-knee attack: bigger steps while far from zero attenuation.
+                let step = inc + (self.attenuation >> 4);
+                self.attenuation = self.attenuation.saturating_sub(step);
+            } else {
+                self.attenuation = (self.attenuation + inc).min(ENVELOPE_MAX_ATTEN);
+            }
+        }
+        let att_db = self.attenuation as f64 * ENVELOPE_DB_PER_STEP;
+        10f64.powf(-att_db / 20.0)
+    }
+}
+
+/// A single DDS tone: a frequency offset from the DDC center and its linear
+/// (full-scale-fraction) amplitude. Each tone gets its own persistent
+/// 64-bit phase accumulator in [`SignalGenerator::tone_phases`] (primary
+/// tone first, then `extra_tones` in order). Slots are matched to tones by
+/// position: adding tones seeds only the newly appended slots and removing
+/// tones drops the trailing ones, never disturbing the remaining slots'
+/// phase continuity.
+#[derive(Debug, Clone, Copy)]
+pub struct ToneSpec {
+    pub offset_hz: f64,
+    pub amplitude: f64,
+}
+
+/// Tuning word for a tone at `offset_hz` against `sample_rate`: how many
+/// turns (scaled to `u64`) the phase advances per sample. Signed so negative
+/// offsets wrap the accumulator the other way.
+fn tuning_word_for(offset_hz: f64, sample_rate: u32) -> u64 {
+    let turns_per_sample = offset_hz / sample_rate as f64;
+    (turns_per_sample * PHASE_SCALE).round() as i64 as u64
+}
+
 pub struct SignalGenerator {
     pub sample_rate: u32,
     pub tone_offset_hz: f64,
     pub noise_level: f64,
     pub amplitude: f64,
-    phase: HashMap<usize, f64>,
+    /// Additional simultaneous tones beyond the primary `tone_offset_hz` /
+    /// `amplitude` pair, e.g. for multi-tone intermod test signals.
+    extra_tones: Vec<ToneSpec>,
+    /// Per-DDC tone phase accumulators (one per active tone, primary first
+    /// then `extra_tones` in order), each seeded from `abs_sample_index * dphi`
+    /// only when its slot is first created and wrapping-added every
+    /// sample after that, so retuning a tone (or adding/removing one) never
+    /// snaps the running phase back to "as if the new frequency had been
+    /// playing since sample 0" - see [`Self::generate_iq`].
+    tone_phases: HashMap<usize, Vec<u64>>,
+    /// Per-DDC envelope state, keyed by `ddc_index` so each DDC's tone is
+    /// gated on its own independent timeline.
+    envelopes: HashMap<usize, CwEnvelope>,
+    cw_rise_ms: f64,
+    cw_fall_ms: f64,
+    /// When `false` (the default), the envelope opens once at startup and
+    /// stays fully open, so the RX test tone is continuously audible without
+    /// asserting PTT. When `true`, the envelope tracks live PTT instead, so
+    /// each key-down/up transition is shaped to suppress key clicks.
+    cw_keyed: bool,
+    /// Channel filter response applied to the combined tone+noise stream.
+    /// `FilterResponse::None` (the default) leaves samples untouched.
+    filter_response: FilterResponse,
+    filter_cutoff_hz: f64,
+    filter_q: f64,
+    /// Per-DDC filter state, built lazily so each DDC's passband has its own
+    /// continuous history.
+    filters: HashMap<usize, IirFilter>,
+    /// Shapes the synthetic noise floor itself (as opposed to `filter_response`,
+    /// which shapes the combined tone+noise stream). `FilterResponse::None`
+    /// (the default) leaves the noise white.
+    noise_shape: FilterResponse,
+    noise_shape_cutoff_hz: f64,
+    noise_shape_q: f64,
+    /// Per-DDC noise-shaping filter state, independent of `filters` so a
+    /// band-limited noise floor and a separate channel filter can run at the
+    /// same time without sharing history.
+    noise_filters: HashMap<usize, IirFilter>,
+    /// `sample_rate` as of the last time `filters`/`noise_filters` were
+    /// built, so a sample-rate change can be detected and both caches
+    /// invalidated before stale-rate coefficients leak into the new stream.
+    filter_sample_rate: u32,
+    /// `tone_offset_hz` as of the last time `filters` was built. `build_filter`
+    /// centers band-pass/notch responses on the tone, so retuning it needs to
+    /// invalidate the cache the same way a sample-rate change does.
+    filter_tone_offset_hz: f64,
 }
 
 impl SignalGenerator {
     pub fn new(sample_rate: u32, tone_offset_hz: f64, noise_level: f64) -> Self {
+        Self::with_cw_rates(sample_rate, tone_offset_hz, noise_level, 5.0, 5.0, false)
+    }
+
+    pub fn with_cw_rates(
+        sample_rate: u32,
+        tone_offset_hz: f64,
+        noise_level: f64,
+        cw_rise_ms: f64,
+        cw_fall_ms: f64,
+        cw_keyed: bool,
+    ) -> Self {
         Self {
             sample_rate,
             tone_offset_hz,
             noise_level,
             amplitude: 0.3,
-            phase: HashMap::new(),
+            extra_tones: Vec::new(),
+            tone_phases: HashMap::new(),
+            envelopes: HashMap::new(),
+            cw_rise_ms,
+            cw_fall_ms,
+            cw_keyed,
+            filter_response: FilterResponse::None,
+            filter_cutoff_hz: 3000.0,
+            filter_q: std::f64::consts::FRAC_1_SQRT_2,
+            filters: HashMap::new(),
+            noise_shape: FilterResponse::None,
+            noise_shape_cutoff_hz: 3000.0,
+            noise_shape_q: std::f64::consts::FRAC_1_SQRT_2,
+            noise_filters: HashMap::new(),
+            filter_sample_rate: sample_rate,
+            filter_tone_offset_hz: tone_offset_hz,
         }
     }
 
-    pub fn generate_iq(&mut self, n_samples: usize, ddc_index: usize) -> Vec<Complex<f64>> {
+    /// Selects the channel filter response. Clears any existing per-DDC
+    /// filter state so the new response starts from a clean slate.
+    pub fn set_filter(&mut self, response: FilterResponse, cutoff_hz: f64, q: f64) {
+        self.filter_response = response;
+        self.filter_cutoff_hz = cutoff_hz;
+        self.filter_q = q;
+        self.filters.clear();
+    }
+
+    /// Selects the noise-shaping response driving the synthetic noise floor.
+    /// Clears any existing per-DDC noise-filter state so the new response
+    /// starts from a clean slate.
+    pub fn set_noise_shape(&mut self, response: FilterResponse, center_hz: f64, q: f64) {
+        self.noise_shape = response;
+        self.noise_shape_cutoff_hz = center_hz;
+        self.noise_shape_q = q;
+        self.noise_filters.clear();
+    }
+
+    fn build_noise_filter(&self) -> Option<IirFilter> {
+        let sr = self.sample_rate as f64;
+        match self.noise_shape {
+            FilterResponse::None => None,
+            FilterResponse::LowPass => Some(IirFilter::low_pass(
+                sr,
+                self.noise_shape_cutoff_hz,
+                self.noise_shape_q,
+            )),
+            FilterResponse::BandPass => Some(IirFilter::band_pass(
+                sr,
+                self.noise_shape_cutoff_hz,
+                self.noise_shape_q,
+            )),
+            FilterResponse::Notch => Some(IirFilter::notch(
+                sr,
+                self.noise_shape_cutoff_hz,
+                self.noise_shape_q,
+            )),
+        }
+    }
+
+    /// If `sample_rate` or `tone_offset_hz` (which `build_filter` uses as the
+    /// band-pass/notch center) has changed since the active filters were
+    /// built, recomputes coefficients for the per-DDC channel filter cache in
+    /// place (cheaper than dropping and lazily reallocating every entry) so
+    /// stale coefficients and their transient history don't leak into the
+    /// new configuration. A tone change resets the channel filters whatever
+    /// their response, low-pass included; the noise-filter cache is only
+    /// recomputed on a sample-rate change.
+    fn invalidate_filters_on_rate_change(&mut self) {
+        let rate_changed = self.sample_rate != self.filter_sample_rate;
+        let tone_changed = self.tone_offset_hz != self.filter_tone_offset_hz;
+
+        if rate_changed || tone_changed {
+            self.filter_sample_rate = self.sample_rate;
+            self.filter_tone_offset_hz = self.tone_offset_hz;
+
+            match self.build_filter() {
+                Some(new_filter) => {
+                    let sections = new_filter.into_sections();
+                    for filter in self.filters.values_mut() {
+                        filter.reconfigure(sections.clone());
+                    }
+                }
+                None => self.filters.clear(),
+            }
+        }
+
+        if rate_changed {
+            match self.build_noise_filter() {
+                Some(new_filter) => {
+                    let sections = new_filter.into_sections();
+                    for filter in self.noise_filters.values_mut() {
+                        filter.reconfigure(sections.clone());
+                    }
+                }
+                None => self.noise_filters.clear(),
+            }
+        }
+    }
+
+    fn build_filter(&self) -> Option<IirFilter> {
+        let sr = self.sample_rate as f64;
+        match self.filter_response {
+            FilterResponse::None => None,
+            FilterResponse::LowPass => Some(IirFilter::low_pass(sr, self.filter_cutoff_hz, self.filter_q)),
+            FilterResponse::BandPass => Some(IirFilter::band_pass(
+                sr,
+                self.tone_offset_hz.abs().max(1.0),
+                self.filter_q,
+            )),
+            FilterResponse::Notch => Some(IirFilter::notch(
+                sr,
+                self.tone_offset_hz.abs().max(1.0),
+                self.filter_q,
+            )),
+        }
+    }
+
+    /// Adds an extra simultaneous tone at `offset_hz` (full-scale-fraction
+    /// `amplitude`) alongside the primary `tone_offset_hz` tone.
+    pub fn add_tone(&mut self, offset_hz: f64, amplitude: f64) {
+        self.extra_tones.push(ToneSpec { offset_hz, amplitude });
+    }
+
+    /// Removes every extra tone added via [`Self::add_tone`], leaving only
+    /// the primary `tone_offset_hz` tone.
+    pub fn clear_extra_tones(&mut self) {
+        self.extra_tones.clear();
+    }
+
+    /// Extra tones added via [`Self::add_tone`], beyond the primary tone.
+    pub fn extra_tones(&self) -> &[ToneSpec] {
+        &self.extra_tones
+    }
+
+    /// The primary tone plus every extra tone, as a single list for
+    /// [`Self::generate_iq`] to sum.
+    fn active_tones(&self) -> Vec<ToneSpec> {
+        let mut tones = Vec::with_capacity(1 + self.extra_tones.len());
+        tones.push(ToneSpec {
+            offset_hz: self.tone_offset_hz,
+            amplitude: self.amplitude,
+        });
+        tones.extend_from_slice(&self.extra_tones);
+        tones
+    }
+
+    /// Generates `n_samples` of IQ for `ddc_index`, starting at
+    /// `abs_sample_index` on the shared [`RadioState`] sample clock. Each
+    /// active tone (see [`ToneSpec`]) has a persistent 64-bit phase
+    /// accumulator in `tone_phases`, seeded from `abs_sample_index *
+    /// tuning_word` only when its slot is first created, i.e. on the first
+    /// call for this DDC or the first call after the tone was added (so it
+    /// starts in step with every other DDC and the echo buffer) and wrapping-added
+    /// every sample after that. Carrying the accumulator forward like this
+    /// (rather than re-deriving it from `abs_sample_index` on every call)
+    /// means retuning a tone mid-stream changes its rate of rotation without
+    /// snapping its phase, exactly like a real NCO. Tones are summed and, if
+    /// their combined amplitude would exceed full scale, scaled down
+    /// together (never clipped) before [`pack_iq_24bit_into`] sees them.
+    /// `keyed` is the raw PTT state; it only reaches the CW envelope (see
+    /// [`CwEnvelope`]) when `cw_keyed` mode is on, otherwise the envelope is
+    /// driven open and left there so the test tone stays continuously
+    /// audible without asserting PTT. Noise is added after gating so the
+    /// noise floor isn't keyed along with the carrier.
+    pub fn generate_iq(
+        &mut self,
+        n_samples: usize,
+        ddc_index: usize,
+        keyed: bool,
+        abs_sample_index: u64,
+    ) -> Vec<Complex<f64>> {
         let normal = Normal::new(0.0, 1.0).unwrap();
         let mut rng = rand::thread_rng();
 
-        let phase = *self.phase.entry(ddc_index).or_insert(0.0);
-        let sr = self.sample_rate as f64;
+        self.invalidate_filters_on_rate_change();
+
+        let sample_rate = self.sample_rate;
+        let tones = self.active_tones();
+        let amp_sum: f64 = tones.iter().map(|t| t.amplitude.abs()).sum();
+        let scale = if amp_sum > 1.0 { 1.0 / amp_sum } else { 1.0 };
+
+        let dphis: Vec<u64> = tones
+            .iter()
+            .map(|t| tuning_word_for(t.offset_hz, sample_rate))
+            .collect();
+
+        let phases = self.tone_phases.entry(ddc_index).or_insert_with(Vec::new);
+        phases.truncate(dphis.len());
+        while phases.len() < dphis.len() {
+            let i = phases.len();
+            phases.push(dphis[i].wrapping_mul(abs_sample_index));
+        }
+
+        let (cw_rise_ms, cw_fall_ms) = (self.cw_rise_ms, self.cw_fall_ms);
+        let envelope = self
+            .envelopes
+            .entry(ddc_index)
+            .or_insert_with(|| CwEnvelope::new(sample_rate, cw_rise_ms, cw_fall_ms));
 
         let mut samples = Vec::with_capacity(n_samples);
-        for i in 0..n_samples {
-            let t = (i as f64 / sr) + phase;
-            let angle = 2.0 * PI * self.tone_offset_hz * t;
-            let tone = Complex::new(angle.cos(), angle.sin()) * self.amplitude;
-            let noise = Complex::new(
+        let mut noise_samples = Vec::with_capacity(n_samples);
+        for _ in 0..n_samples {
+            let gain = envelope.step(keyed || !self.cw_keyed);
+            let mut combined = Complex::new(0.0, 0.0);
+            for (i, tone) in tones.iter().enumerate() {
+                let theta = 2.0 * PI * (phases[i] >> 11) as f64 / (1u64 << 53) as f64;
+                combined += Complex::new(theta.cos(), theta.sin()) * tone.amplitude;
+                phases[i] = phases[i].wrapping_add(dphis[i]);
+            }
+            samples.push(combined * scale * gain);
+            noise_samples.push(Complex::new(
                 normal.sample(&mut rng) * self.noise_level,
                 normal.sample(&mut rng) * self.noise_level,
-            );
-            samples.push(tone + noise);
+            ));
         }
 
-        let new_phase = phase + n_samples as f64 / sr;
-        let stored = self.phase.get_mut(&ddc_index).unwrap();
-        *stored = if new_phase > 1e6 {
-            if self.tone_offset_hz != 0.0 {
-                new_phase % (1.0 / self.tone_offset_hz)
-            } else {
-                0.0
-            }
-        } else {
-            new_phase
-        };
+        if self.noise_shape != FilterResponse::None {
+            let new_filter = self.build_noise_filter();
+            let filter = self
+                .noise_filters
+                .entry(ddc_index)
+                .or_insert_with(|| new_filter.expect("noise_shape != None"));
+            filter.process_block(&mut noise_samples);
+        }
+
+        for (sample, noise) in samples.iter_mut().zip(noise_samples) {
+            *sample += noise;
+        }
+
+        if self.filter_response != FilterResponse::None {
+            let new_filter = self.build_filter();
+            let filter = self
+                .filters
+                .entry(ddc_index)
+                .or_insert_with(|| new_filter.expect("filter_response != None"));
+            filter.process_block(&mut samples);
+        }
 
         samples
     }
@@ -257,15 +691,119 @@ impl SignalGenerator {
 
 const ECHO_ATTENUATION_DB: f64 = 60.0;
 
+/// Result of checking a playback window against a [`SampleRangeSet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coverage {
+    Empty,
+    Partial,
+    Full,
+}
+
+/// A sorted, coalescing set of non-overlapping half-open `[start, end)`
+/// sample-index intervals. [`EchoBuffer`] uses one per recorded frequency to
+/// track exactly which stretches of a committed buffer actually contain
+/// captured audio, so playback can fall back to silence instead of stale
+/// buffer contents for anything outside those spans.
+#[derive(Debug, Clone, Default)]
+struct SampleRangeSet {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl SampleRangeSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `[start, end)`, merging it with any overlapping or adjacent
+    /// existing interval so the set stays sorted and non-overlapping.
+    fn insert(&mut self, start: u64, end: u64) {
+        if start >= end {
+            return;
+        }
+        self.ranges.push((start, end));
+        self.ranges.sort_unstable_by_key(|&(s, _)| s);
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.ranges.len());
+        for &(s, e) in &self.ranges {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                _ => merged.push((s, e)),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    /// Drops (or trims) every interval outside `[0, len)`, e.g. after the
+    /// backing buffer has been truncated.
+    fn clip(&mut self, len: u64) {
+        for (_, e) in self.ranges.iter_mut() {
+            *e = (*e).min(len);
+        }
+        self.ranges.retain(|&(s, e)| s < e);
+    }
+
+    fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    fn contains(&self, idx: u64) -> bool {
+        self.ranges
+            .binary_search_by(|&(s, e)| {
+                if idx < s {
+                    std::cmp::Ordering::Greater
+                } else if idx >= e {
+                    std::cmp::Ordering::Less
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    /// Whether `[start, end)` is fully covered, partially covered, or has no
+    /// overlap with the recorded spans.
+    fn covers(&self, start: u64, end: u64) -> Coverage {
+        if start >= end {
+            return Coverage::Empty;
+        }
+        let covered: u64 = self
+            .ranges
+            .iter()
+            .map(|&(s, e)| {
+                let lo = s.max(start);
+                let hi = e.min(end);
+                hi.saturating_sub(lo)
+            })
+            .sum();
+        if covered == 0 {
+            Coverage::Empty
+        } else if covered >= end - start {
+            Coverage::Full
+        } else {
+            Coverage::Partial
+        }
+    }
+}
+
 pub struct EchoBuffer {
     pub sample_rate: u32,
-    pub max_duration: f64,
+    /// Maximum recording length, in femtoseconds, so it converts to a sample
+    /// count via exact integer arithmetic rather than `f64` rounding.
+    pub max_duration_fs: FemtoDuration,
     attenuation: f64,
     echoes: HashMap<u32, Vec<Complex<f64>>>,
+    /// Which sample spans of each committed `echoes` buffer actually contain
+    /// recorded audio (as opposed to the zero-fill a partial capture would
+    /// otherwise leave behind).
+    coverage: HashMap<u32, SampleRangeSet>,
     playback_pos: HashMap<u32, usize>,
-    shift_phase: HashMap<u32, f64>, // per-freq angle accumulator (radians)
+    /// Per-recorded-freq frequency-shift phase accumulator, seeded from
+    /// `abs_sample_index * dphi` only the first time a freq needs shifting
+    /// and wrapping-added every sample after that, so retuning the RX VFO
+    /// never snaps the shifter's running phase (see [`Self::generate_echo`]).
+    shift_phase: HashMap<u32, u64>,
     recording: Vec<Complex<f64>>,
     recording_freq: u32,
+    recording_coverage: SampleRangeSet,
     is_recording: bool,
 }
 
@@ -274,13 +812,15 @@ impl EchoBuffer {
         let attenuation = 10.0_f64.powf(-ECHO_ATTENUATION_DB / 20.0);
         Self {
             sample_rate,
-            max_duration: 10.0,
+            max_duration_fs: 10 * FEMTOS_PER_SEC,
             attenuation,
             echoes: HashMap::new(),
+            coverage: HashMap::new(),
             playback_pos: HashMap::new(),
             shift_phase: HashMap::new(),
             recording: Vec::new(),
             recording_freq: 0,
+            recording_coverage: SampleRangeSet::new(),
             is_recording: false,
         }
     }
@@ -290,6 +830,7 @@ impl EchoBuffer {
             self.commit();
         }
         self.recording.clear();
+        self.recording_coverage.clear();
         self.recording_freq = tx_freq;
         self.is_recording = true;
         log::info!("Echo: recording started on {} Hz", tx_freq);
@@ -299,7 +840,9 @@ impl EchoBuffer {
         if !self.is_recording || samples.is_empty() {
             return;
         }
+        let start = self.recording.len() as u64;
         self.recording.extend_from_slice(samples);
+        self.recording_coverage.insert(start, self.recording.len() as u64);
     }
 
     pub fn stop_recording(&mut self) {
@@ -317,14 +860,19 @@ impl EchoBuffer {
         if freq == 0 {
             log::debug!("Echo: discarding recording with freq=0");
             self.recording.clear();
+            self.recording_coverage.clear();
             return;
         }
-        let max_samples = (self.sample_rate as f64 * self.max_duration) as usize;
+        let max_samples =
+            (self.sample_rate as FemtoDuration * self.max_duration_fs / FEMTOS_PER_SEC) as usize;
         let mut buf = std::mem::take(&mut self.recording);
         buf.truncate(max_samples);
+        let mut coverage = std::mem::take(&mut self.recording_coverage);
         if buf.is_empty() {
+            coverage.clear();
             return;
         }
+        coverage.clip(buf.len() as u64);
         let len = buf.len();
         log::info!(
             "Echo: committed {} samples ({:.2}s) on {} Hz",
@@ -334,13 +882,36 @@ impl EchoBuffer {
         );
         self.echoes.insert(freq, buf);
         self.playback_pos.insert(freq, 0);
+        self.coverage.insert(freq, coverage);
     }
 
+    /// Reports, for the committed recording on `freq` (if any), the
+    /// `[start, end)` sample ranges within that buffer that actually contain
+    /// captured audio. Lets SCPI/MQTT report capture coverage and lets
+    /// callers assert exactly which sample ranges were echoed.
+    pub fn coverage_ranges(&self, freq: u32) -> Option<&[(u64, u64)]> {
+        self.coverage.get(&freq).map(|c| c.ranges.as_slice())
+    }
+
+    /// Frequencies with a committed recording, for telemetry enumeration.
+    pub fn recorded_frequencies(&self) -> impl Iterator<Item = u32> + '_ {
+        self.echoes.keys().copied()
+    }
+
+    /// `abs_sample_index` is the shared [`RadioState`] sample clock value at
+    /// the start of this block. Each recorded freq's frequency-shift
+    /// oscillator keeps a persistent phase in `shift_phase`, seeded from
+    /// `abs_sample_index * dphi` only the first time that freq needs
+    /// shifting and wrapping-added every sample after that (never
+    /// re-derived from scratch), so retuning the RX VFO changes the shift
+    /// rate without snapping its phase, in lock-step with how
+    /// [`SignalGenerator`]'s tone now behaves.
     pub fn generate_echo(
         &mut self,
         n_samples: usize,
         rx_freq: u32,
         sample_rate: u32,
+        abs_sample_index: u64,
     ) -> Vec<Complex<f64>> {
         if self.echoes.is_empty() {
             return vec![Complex::new(0.0, 0.0); n_samples];
@@ -358,6 +929,8 @@ impl EchoBuffer {
 
             let echo_buf = self.echoes.get(&freq).unwrap();
             let echo_len = echo_buf.len();
+            let empty_coverage = SampleRangeSet::new();
+            let coverage = self.coverage.get(&freq).unwrap_or(&empty_coverage);
             let mut pos = *self.playback_pos.get(&freq).unwrap_or(&0);
 
             let mut chunk = vec![Complex::new(0.0, 0.0); n_samples];
@@ -365,29 +938,45 @@ impl EchoBuffer {
             let mut write_pos = 0;
             while remaining > 0 {
                 let available = remaining.min(echo_len - pos);
-                chunk[write_pos..write_pos + available]
-                    .copy_from_slice(&echo_buf[pos..pos + available]);
+                // Only copy samples the recorded coverage actually vouches
+                // for; anything outside it plays back as silence rather than
+                // leftover buffer contents from a shorter prior recording.
+                match coverage.covers(pos as u64, (pos + available) as u64) {
+                    Coverage::Full => {
+                        chunk[write_pos..write_pos + available]
+                            .copy_from_slice(&echo_buf[pos..pos + available]);
+                    }
+                    Coverage::Partial => {
+                        for k in 0..available {
+                            if coverage.contains((pos + k) as u64) {
+                                chunk[write_pos + k] = echo_buf[pos + k];
+                            }
+                        }
+                    }
+                    Coverage::Empty => {}
+                }
                 pos = (pos + available) % echo_len;
                 write_pos += available;
                 remaining -= available;
             }
             self.playback_pos.insert(freq, pos);
 
-            // Frequency-shift: track accumulated angle (radians) so the
-            // shift oscillator transitions smoothly when offset changes.
+            // Frequency-shift: a persistent wrapping phase accumulator (see
+            // `shift_phase`), so the shift oscillator never drifts regardless
+            // of run length and never jumps when the RX VFO retunes.
             if offset_hz != 0.0 {
                 let sr = sample_rate as f64;
-                let phase0 = *self.shift_phase.get(&freq).unwrap_or(&0.0);
-                let step = 2.0 * PI * offset_hz / sr;
-                for (i, s) in chunk.iter_mut().enumerate() {
-                    let angle = phase0 + step * i as f64;
-                    *s *= Complex::new(angle.cos(), angle.sin());
+                let dphi = ((offset_hz / sr) * PHASE_SCALE).round() as i64 as u64;
+                let mut phase = *self
+                    .shift_phase
+                    .entry(freq)
+                    .or_insert_with(|| dphi.wrapping_mul(abs_sample_index));
+                for s in chunk.iter_mut() {
+                    let theta = 2.0 * PI * (phase >> 11) as f64 / (1u64 << 53) as f64;
+                    *s *= Complex::new(theta.cos(), theta.sin());
+                    phase = phase.wrapping_add(dphi);
                 }
-                let mut new_phase = phase0 + step * n_samples as f64;
-                if new_phase.abs() > 1e6 {
-                    new_phase %= 2.0 * PI;
-                }
-                self.shift_phase.insert(freq, new_phase);
+                self.shift_phase.insert(freq, phase);
             }
 
             for (i, s) in chunk.iter().enumerate() {
@@ -441,3 +1030,109 @@ pub fn unpack_tx_iq_16bit(data: &[u8]) -> Vec<Complex<f64>> {
     }
     samples
 }
+
+// ---------------------------------------------------------------------------
+// Synthetic AIN telemetry
+// ---------------------------------------------------------------------------
+
+/// Synthetic analog-in readings (exciter/forward/reverse power, PA volts and
+/// current, supply rail) reported in the Protocol 1 C&C response bytes and
+/// mirrored out over MQTT telemetry. Kept as a single computation so both
+/// consumers always agree.
+#[derive(Debug, Clone, Copy)]
+pub struct AinReadings {
+    pub exciter_power: u16,
+    pub forward_power: u16,
+    pub reverse_power: u16,
+    pub pa_volts: u16,
+    pub pa_amps: u16,
+    pub supply_volts: u16,
+}
+
+pub fn compute_ain_readings(ptt: bool, tx_drive: u8) -> AinReadings {
+    let d = tx_drive as u16;
+    let (exciter_power, forward_power) = if ptt { (d * 10, (d * d) >> 4) } else { (0, 0) };
+    let reverse_power = if ptt { (forward_power / 50).max(1) } else { 0 };
+    let pa_amps = if ptt { d * 5 } else { 0 };
+    AinReadings {
+        exciter_power,
+        forward_power,
+        reverse_power,
+        pa_volts: 3200,
+        pa_amps,
+        supply_volts: 3200,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_range_set_starts_empty() {
+        let set = SampleRangeSet::new();
+        assert_eq!(set.covers(0, 100), Coverage::Empty);
+        assert!(!set.contains(0));
+    }
+
+    #[test]
+    fn sample_range_set_insert_is_queryable() {
+        let mut set = SampleRangeSet::new();
+        set.insert(10, 20);
+        assert!(set.contains(10));
+        assert!(set.contains(19));
+        assert!(!set.contains(9));
+        assert!(!set.contains(20));
+        assert_eq!(set.covers(10, 20), Coverage::Full);
+        assert_eq!(set.covers(0, 30), Coverage::Partial);
+        assert_eq!(set.covers(100, 200), Coverage::Empty);
+    }
+
+    #[test]
+    fn sample_range_set_coalesces_overlapping_and_adjacent_inserts() {
+        let mut set = SampleRangeSet::new();
+        set.insert(0, 10);
+        set.insert(10, 20); // adjacent, should merge into one span
+        set.insert(15, 25); // overlapping, should merge too
+        assert_eq!(set.ranges, vec![(0, 25)]);
+        assert_eq!(set.covers(0, 25), Coverage::Full);
+    }
+
+    #[test]
+    fn sample_range_set_keeps_disjoint_ranges_separate() {
+        let mut set = SampleRangeSet::new();
+        set.insert(0, 10);
+        set.insert(20, 30);
+        assert_eq!(set.ranges, vec![(0, 10), (20, 30)]);
+        assert_eq!(set.covers(0, 30), Coverage::Partial);
+        assert_eq!(set.covers(10, 20), Coverage::Empty);
+    }
+
+    #[test]
+    fn sample_range_set_insert_ignores_empty_range() {
+        let mut set = SampleRangeSet::new();
+        set.insert(10, 10);
+        set.insert(20, 5);
+        assert!(set.ranges.is_empty());
+    }
+
+    #[test]
+    fn sample_range_set_clip_trims_and_drops_ranges() {
+        let mut set = SampleRangeSet::new();
+        set.insert(0, 10);
+        set.insert(20, 30);
+        set.clip(25);
+        assert_eq!(set.ranges, vec![(0, 10), (20, 25)]);
+        set.clip(5);
+        assert_eq!(set.ranges, vec![(0, 5)]);
+    }
+
+    #[test]
+    fn sample_range_set_clear_empties_all_ranges() {
+        let mut set = SampleRangeSet::new();
+        set.insert(0, 10);
+        set.clear();
+        assert!(set.ranges.is_empty());
+        assert_eq!(set.covers(0, 10), Coverage::Empty);
+    }
+}
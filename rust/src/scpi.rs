@@ -0,0 +1,323 @@
+//! Runtime SCPI control server.
+//!
+//! Accepts SCPI-style text commands over TCP (one per line) so the emulator
+//! can be scripted from test harnesses instead of only being driven by the
+//! HPSDR host's `process_control` bytes. Colon-delimited hierarchical
+//! keywords, comma-separated args, a `?` suffix marks a query, newline
+//! terminated. Examples:
+//!
+//! ```text
+//! SIGGEN:TONE 1000,-40
+//! SIGGEN:ADDTONE 2500,-50
+//! SIGGEN:CLEARTONES
+//! RADIO:SAMPLERATE 192000
+//! RADIO:PTT ON
+//! RADIO:TXDRIVE?
+//! RADIO:SAMPLECLOCK?
+//! ECHO:COVERAGE? 14200000
+//! ```
+//!
+//! `SIGGEN:TONE` and `SIGGEN:ADDTONE` take a frequency offset in Hz from
+//! each DDC's center (clamped to +/- sample_rate/2) and a level in dBFS.
+//!
+//! Every non-blank line gets exactly one response line back: `OK`, a bare
+//! value for queries, or `ERR <reason>`. Blank lines get no response.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use crate::radio::{code_to_sample_rate, sample_rate_to_code, EchoBuffer, RadioState, SignalGenerator};
+
+struct ScpiCommand {
+    path: Vec<String>,
+    is_query: bool,
+    args: Vec<String>,
+}
+
+fn parse_command(line: &str) -> Option<ScpiCommand> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let (cmd_part, arg_part) = match line.split_once(char::is_whitespace) {
+        Some((c, a)) => (c, a.trim()),
+        None => (line, ""),
+    };
+    let is_query = cmd_part.ends_with('?');
+    let cmd_clean = cmd_part.trim_end_matches('?');
+    let path = cmd_clean.split(':').map(|s| s.to_uppercase()).collect();
+    let args = if arg_part.is_empty() {
+        Vec::new()
+    } else {
+        arg_part.split(',').map(|s| s.trim().to_string()).collect()
+    };
+    Some(ScpiCommand { path, is_query, args })
+}
+
+/// Parses a `RX<n>` keyword segment into a DDC index.
+fn parse_rx_index(segment: &str) -> Option<usize> {
+    segment.strip_prefix("RX")?.parse().ok()
+}
+
+async fn dispatch(
+    cmd: &ScpiCommand,
+    state: &Arc<Mutex<RadioState>>,
+    siggen: &Arc<Mutex<SignalGenerator>>,
+    echo: &Arc<Mutex<Option<EchoBuffer>>>,
+) -> String {
+    let path: Vec<&str> = cmd.path.iter().map(String::as_str).collect();
+
+    match path.as_slice() {
+        ["RADIO", "SAMPLERATE"] => {
+            if cmd.is_query {
+                state.lock().await.sample_rate.to_string()
+            } else {
+                match cmd.args.first().and_then(|a| a.parse::<u32>().ok()) {
+                    Some(rate) if code_to_sample_rate(sample_rate_to_code(rate)) == Some(rate) => {
+                        state.lock().await.sample_rate = rate;
+                        siggen.lock().await.sample_rate = rate;
+                        "OK".to_string()
+                    }
+                    _ => "ERR invalid sample rate".to_string(),
+                }
+            }
+        }
+        ["RADIO", "PTT"] => {
+            if cmd.is_query {
+                if state.lock().await.ptt { "ON" } else { "OFF" }.to_string()
+            } else {
+                match cmd.args.first().map(|a| a.to_uppercase()).as_deref() {
+                    Some("ON") => {
+                        state.lock().await.ptt = true;
+                        "OK".to_string()
+                    }
+                    Some("OFF") => {
+                        state.lock().await.ptt = false;
+                        "OK".to_string()
+                    }
+                    _ => "ERR expected ON|OFF".to_string(),
+                }
+            }
+        }
+        ["RADIO", "TXFREQ"] => {
+            if cmd.is_query {
+                state.lock().await.tx_frequency.to_string()
+            } else {
+                match cmd.args.first().and_then(|a| a.parse::<u32>().ok()) {
+                    Some(freq) => {
+                        state.lock().await.tx_frequency = freq;
+                        "OK".to_string()
+                    }
+                    None => "ERR invalid frequency".to_string(),
+                }
+            }
+        }
+        ["RADIO", "SAMPLECLOCK"] => {
+            if cmd.is_query {
+                state.lock().await.sample_clock().to_string()
+            } else {
+                "ERR RADIO:SAMPLECLOCK is read-only".to_string()
+            }
+        }
+        ["RADIO", "TXDRIVE"] => {
+            if cmd.is_query {
+                state.lock().await.tx_drive.to_string()
+            } else {
+                match cmd.args.first().and_then(|a| a.parse::<u8>().ok()) {
+                    Some(drive) => {
+                        state.lock().await.tx_drive = drive;
+                        "OK".to_string()
+                    }
+                    None => "ERR invalid drive level, expected 0-255".to_string(),
+                }
+            }
+        }
+        [rx, "FREQ"] => match parse_rx_index(rx) {
+            Some(idx) => {
+                let mut s = state.lock().await;
+                if idx >= s.rx_frequencies.len() {
+                    "ERR DDC index out of range".to_string()
+                } else if cmd.is_query {
+                    s.rx_frequencies[idx].to_string()
+                } else {
+                    match cmd.args.first().and_then(|a| a.parse::<u32>().ok()) {
+                        Some(freq) => {
+                            s.rx_frequencies[idx] = freq;
+                            "OK".to_string()
+                        }
+                        None => "ERR invalid frequency".to_string(),
+                    }
+                }
+            }
+            None => "ERR unknown command".to_string(),
+        },
+        [rx, "ATTEN"] => match parse_rx_index(rx) {
+            Some(idx) => {
+                let mut s = state.lock().await;
+                if idx >= s.rx_attenuators.len() {
+                    "ERR DDC index out of range".to_string()
+                } else if cmd.is_query {
+                    format!("{:.1}", s.rx_attenuators[idx].db())
+                } else {
+                    match cmd.args.first().and_then(|a| a.parse::<f64>().ok()) {
+                        Some(db) if db >= 0.0 => {
+                            s.rx_attenuators[idx].set_half_db_code((db * 2.0).round() as u8);
+                            "OK".to_string()
+                        }
+                        _ => "ERR invalid attenuation, expected 0-31.5".to_string(),
+                    }
+                }
+            }
+            None => "ERR unknown command".to_string(),
+        },
+        ["SIGGEN", "NOISE"] => {
+            if cmd.is_query {
+                format!("{:e}", siggen.lock().await.noise_level)
+            } else {
+                match cmd.args.first().and_then(|a| a.parse::<f64>().ok()) {
+                    Some(level) if level >= 0.0 => {
+                        siggen.lock().await.noise_level = level;
+                        "OK".to_string()
+                    }
+                    _ => "ERR invalid noise level, expected >= 0".to_string(),
+                }
+            }
+        }
+        ["SIGGEN", "TONE"] => {
+            if cmd.is_query {
+                let sg = siggen.lock().await;
+                let level_db = 20.0 * sg.amplitude.max(1e-12).log10();
+                format!("{},{:.1}", sg.tone_offset_hz, level_db)
+            } else {
+                let freq = cmd.args.first().and_then(|a| a.parse::<f64>().ok());
+                let level_db = cmd.args.get(1).and_then(|a| a.parse::<f64>().ok());
+                match freq {
+                    Some(hz) => {
+                        let mut sg = siggen.lock().await;
+                        let max_offset = sg.sample_rate as f64 / 2.0;
+                        sg.tone_offset_hz = hz.clamp(-max_offset, max_offset);
+                        if let Some(db) = level_db {
+                            sg.amplitude = 10f64.powf(db / 20.0).clamp(0.0, 1.0);
+                        }
+                        "OK".to_string()
+                    }
+                    None => "ERR invalid tone, expected <freq_hz>[,<level_dbfs>]".to_string(),
+                }
+            }
+        }
+        ["SIGGEN", "ADDTONE"] => {
+            if cmd.is_query {
+                siggen
+                    .lock()
+                    .await
+                    .extra_tones()
+                    .iter()
+                    .map(|t| format!("{},{:.1}", t.offset_hz, 20.0 * t.amplitude.max(1e-12).log10()))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            } else {
+                let freq = cmd.args.first().and_then(|a| a.parse::<f64>().ok());
+                let level_db = cmd.args.get(1).and_then(|a| a.parse::<f64>().ok());
+                match freq {
+                    Some(hz) => {
+                        let mut sg = siggen.lock().await;
+                        let max_offset = sg.sample_rate as f64 / 2.0;
+                        let amplitude = level_db.map_or(0.3, |db| 10f64.powf(db / 20.0).clamp(0.0, 1.0));
+                        sg.add_tone(hz.clamp(-max_offset, max_offset), amplitude);
+                        "OK".to_string()
+                    }
+                    None => "ERR invalid tone, expected <freq_hz>[,<level_dbfs>]".to_string(),
+                }
+            }
+        }
+        ["SIGGEN", "CLEARTONES"] => {
+            if cmd.is_query {
+                return "ERR SIGGEN:CLEARTONES is not a query".to_string();
+            }
+            siggen.lock().await.clear_extra_tones();
+            "OK".to_string()
+        }
+        ["ECHO", "COVERAGE"] => {
+            if !cmd.is_query {
+                return "ERR ECHO:COVERAGE is query-only".to_string();
+            }
+            match cmd.args.first().and_then(|a| a.parse::<u32>().ok()) {
+                Some(freq) => match echo.lock().await.as_ref().and_then(|e| e.coverage_ranges(freq)) {
+                    Some(ranges) => ranges
+                        .iter()
+                        .map(|(s, e)| format!("{}-{}", s, e))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    None => String::new(),
+                },
+                None => "ERR invalid frequency".to_string(),
+            }
+        }
+        _ => "ERR unknown command".to_string(),
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    state: Arc<Mutex<RadioState>>,
+    siggen: Arc<Mutex<SignalGenerator>>,
+    echo: Arc<Mutex<Option<EchoBuffer>>>,
+) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut lines = BufReader::new(read_half).lines();
+
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                let Some(cmd) = parse_command(&line) else {
+                    continue;
+                };
+                let reply = dispatch(&cmd, &state, &siggen, &echo).await;
+                if write_half.write_all(format!("{}\n", reply).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("SCPI: read error: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+pub async fn run_scpi(
+    port: u16,
+    state: Arc<Mutex<RadioState>>,
+    siggen: Arc<Mutex<SignalGenerator>>,
+    echo: Arc<Mutex<Option<EchoBuffer>>>,
+) {
+    let listener = match TcpListener::bind(format!("0.0.0.0:{}", port)).await {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("SCPI: failed to bind TCP port {}: {}", port, e);
+            return;
+        }
+    };
+    log::info!("SCPI control server listening on TCP port {}", port);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                log::info!("SCPI: client connected from {}", addr);
+                tokio::spawn(handle_connection(
+                    stream,
+                    Arc::clone(&state),
+                    Arc::clone(&siggen),
+                    Arc::clone(&echo),
+                ));
+            }
+            Err(e) => {
+                log::error!("SCPI: accept error: {}", e);
+            }
+        }
+    }
+}